@@ -0,0 +1,203 @@
+use smoltcp::wire::{IpProtocol, Ipv4Address, Ipv6Address};
+
+/// Implemented by every lookup tuple type so generic table code (e.g. `NatTable`) can read
+/// the protocol without caring whether the tuple is IPv4, IPv6, or a `LookupTuple` wrapping
+/// either.
+pub trait IpTuple {
+    fn protocol(&self) -> IpProtocol;
+}
+
+/// A canonical 5-tuple (protocol, source/destination address, source/destination port) used
+/// to key NAT and conntrack lookups for an IPv4 flow.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct LookupTupleIpv4 {
+    protocol: IpProtocol,
+    src_ip: Ipv4Address,
+    dst_ip: Ipv4Address,
+    src_port: u16,
+    dst_port: u16,
+}
+
+impl LookupTupleIpv4 {
+    pub fn new(
+        protocol: IpProtocol,
+        src_ip: Ipv4Address,
+        dst_ip: Ipv4Address,
+        src_port: u16,
+        dst_port: u16,
+    ) -> Self {
+        LookupTupleIpv4 {
+            protocol,
+            src_ip,
+            dst_ip,
+            src_port,
+            dst_port,
+        }
+    }
+
+    pub fn src_ip(&self) -> Ipv4Address {
+        self.src_ip
+    }
+
+    pub fn dst_ip(&self) -> Ipv4Address {
+        self.dst_ip
+    }
+
+    pub fn src_port(&self) -> u16 {
+        self.src_port
+    }
+
+    pub fn dst_port(&self) -> u16 {
+        self.dst_port
+    }
+}
+
+impl IpTuple for LookupTupleIpv4 {
+    fn protocol(&self) -> IpProtocol {
+        self.protocol
+    }
+}
+
+/// The IPv6 counterpart of `LookupTupleIpv4`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct LookupTupleIpv6 {
+    protocol: IpProtocol,
+    src_ip: Ipv6Address,
+    dst_ip: Ipv6Address,
+    src_port: u16,
+    dst_port: u16,
+}
+
+impl LookupTupleIpv6 {
+    pub fn new(
+        protocol: IpProtocol,
+        src_ip: Ipv6Address,
+        dst_ip: Ipv6Address,
+        src_port: u16,
+        dst_port: u16,
+    ) -> Self {
+        LookupTupleIpv6 {
+            protocol,
+            src_ip,
+            dst_ip,
+            src_port,
+            dst_port,
+        }
+    }
+
+    pub fn src_ip(&self) -> Ipv6Address {
+        self.src_ip
+    }
+
+    pub fn dst_ip(&self) -> Ipv6Address {
+        self.dst_ip
+    }
+
+    pub fn src_port(&self) -> u16 {
+        self.src_port
+    }
+
+    pub fn dst_port(&self) -> u16 {
+        self.dst_port
+    }
+}
+
+impl IpTuple for LookupTupleIpv6 {
+    fn protocol(&self) -> IpProtocol {
+        self.protocol
+    }
+}
+
+/// Either an IPv4 or an IPv6 lookup tuple, so dual-stack code can share one `NatTable`
+/// instead of running parallel v4/v6 tables.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum LookupTuple {
+    V4(LookupTupleIpv4),
+    V6(LookupTupleIpv6),
+}
+
+impl IpTuple for LookupTuple {
+    fn protocol(&self) -> IpProtocol {
+        match self {
+            LookupTuple::V4(tuple) => tuple.protocol(),
+            LookupTuple::V6(tuple) => tuple.protocol(),
+        }
+    }
+}
+
+/// Reads the IP version nibble from `packet` and builds the matching lookup tuple: IPv4
+/// (version 4, 20-byte minimum header, source at offset 12, destination at 16) or IPv6
+/// (version 6, 40-byte header, source at offset 8, destination at 24). Returns None if the
+/// version is unrecognized or the packet is too short for its header.
+pub fn dissect(packet: &[u8], src_port: u16, dst_port: u16) -> Option<LookupTuple> {
+    let version = packet.first()? >> 4;
+    match version {
+        4 if packet.len() >= 20 => {
+            let protocol = IpProtocol::from(packet[9]);
+            let src_ip = Ipv4Address::from_bytes(&packet[12..16]);
+            let dst_ip = Ipv4Address::from_bytes(&packet[16..20]);
+            Some(LookupTuple::V4(LookupTupleIpv4::new(
+                protocol, src_ip, dst_ip, src_port, dst_port,
+            )))
+        }
+        6 if packet.len() >= 40 => {
+            let protocol = IpProtocol::from(packet[6]);
+            let src_ip = Ipv6Address::from_bytes(&packet[8..24]);
+            let dst_ip = Ipv6Address::from_bytes(&packet[24..40]);
+            Some(LookupTuple::V6(LookupTupleIpv6::new(
+                protocol, src_ip, dst_ip, src_port, dst_port,
+            )))
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dissects_ipv4_header() {
+        let mut packet = vec![0u8; 20];
+        packet[0] = 4 << 4;
+        packet[9] = u8::from(IpProtocol::Tcp);
+        packet[12..16].copy_from_slice(&Ipv4Address::new(10, 0, 0, 1).0);
+        packet[16..20].copy_from_slice(&Ipv4Address::new(10, 0, 0, 2).0);
+
+        let tuple = dissect(&packet, 1337, 443).unwrap();
+        assert_eq!(
+            tuple,
+            LookupTuple::V4(LookupTupleIpv4::new(
+                IpProtocol::Tcp,
+                Ipv4Address::new(10, 0, 0, 1),
+                Ipv4Address::new(10, 0, 0, 2),
+                1337,
+                443,
+            ))
+        );
+    }
+
+    #[test]
+    fn dissects_ipv6_header() {
+        let mut packet = vec![0u8; 40];
+        packet[0] = 6 << 4;
+        packet[6] = u8::from(IpProtocol::Udp);
+        let src = Ipv6Address::new(0xfd00, 0, 0, 0, 0, 0, 0, 1);
+        let dst = Ipv6Address::new(0xfd00, 0, 0, 0, 0, 0, 0, 2);
+        packet[8..24].copy_from_slice(&src.0);
+        packet[24..40].copy_from_slice(&dst.0);
+
+        let tuple = dissect(&packet, 53, 53535).unwrap();
+        assert_eq!(
+            tuple,
+            LookupTuple::V6(LookupTupleIpv6::new(IpProtocol::Udp, src, dst, 53, 53535))
+        );
+    }
+
+    #[test]
+    fn rejects_unknown_version_and_short_packets() {
+        assert_eq!(dissect(&[], 0, 0), None);
+        assert_eq!(dissect(&[0x50, 0, 0], 0, 0), None);
+        assert_eq!(dissect(&[0x90; 20], 0, 0), None);
+    }
+}
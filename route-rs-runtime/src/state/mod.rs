@@ -0,0 +1,2 @@
+pub mod nat_table;
+pub mod routing_table;
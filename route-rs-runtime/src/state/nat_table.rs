@@ -1,86 +1,299 @@
-use crate::packet::tuple::LookupTupleIpv4;
-use bimap::BiHashMap;
-use std::sync::RwLock;
+use crate::packet::tuple::{IpTuple, LookupTupleIpv4};
+use smoltcp::wire::{IpProtocol, Ipv4Address};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::ops::Range;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
 
-pub struct NatTable {
-    table: RwLock<BiHashMap<LookupTupleIpv4, LookupTupleIpv4>>,
+/// Idle timeout applied to a mapping when no per-protocol override has been configured.
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// A single conntrack-style mapping. The same entry is indexed by both its internal and
+/// external tuple so either direction of a flow resolves to it, and callers can hang
+/// stateful data `E` (byte/packet counters, TCP state, an owning interface id, ...) off it.
+pub struct ConnectionEntry<T, E> {
+    pub internal: T,
+    pub external: T,
+    last_seen: RwLock<(Instant, Duration)>,
+    data: Arc<E>,
+}
+
+impl<T, E> ConnectionEntry<T, E> {
+    fn touch(&self) {
+        let mut last_seen = self.last_seen.write().unwrap();
+        last_seen.0 = Instant::now();
+    }
+
+    fn is_expired(&self, now: Instant) -> bool {
+        let last_seen = self.last_seen.read().unwrap();
+        now.duration_since(last_seen.0) >= last_seen.1
+    }
 }
 
-impl NatTable {
-    /// Creates a new empty Nat Table
+struct Inner<T, E> {
+    by_internal: HashMap<T, Arc<ConnectionEntry<T, E>>>,
+    by_external: HashMap<T, Arc<ConnectionEntry<T, E>>>,
+}
+
+/// A NAT / conntrack table mapping internal tuples to external tuples and back. Generic
+/// over the tuple type `T` (e.g. `LookupTupleIpv4`, `LookupTupleIpv6`, or the dual-stack
+/// `LookupTuple` enum) so a single table implementation serves v4 and v6 routing alike, and
+/// over `E`, arbitrary per-connection metadata attached to each mapping.
+pub struct NatTable<T, E> {
+    inner: RwLock<Inner<T, E>>,
+    default_timeout: Duration,
+    protocol_timeouts: RwLock<HashMap<IpProtocol, Duration>>,
+}
+
+impl<T: Eq + Hash + Clone, E> NatTable<T, E> {
+    /// Creates a new empty Nat Table with the default idle timeout
     pub fn new() -> Self {
-        let table = RwLock::new(BiHashMap::new());
-        NatTable { table }
+        Self::new_with_timeout(DEFAULT_TIMEOUT)
     }
 
-    /// Insert a set of internal and external tuples into NatTable, returns and
-    /// Error if the value already exists
-    pub fn insert(&self, internal: LookupTupleIpv4, external: LookupTupleIpv4) -> Result<(), ()> {
-        let mut nat_table = self.table.write().unwrap();
-        if nat_table.insert_no_overwrite(internal, external).is_err() {
-            return Err(());
+    /// Creates a new empty Nat Table whose entries expire after `default_timeout` of
+    /// inactivity, unless overridden per-protocol via `set_protocol_timeout`.
+    pub fn new_with_timeout(default_timeout: Duration) -> Self {
+        let inner = Inner {
+            by_internal: HashMap::new(),
+            by_external: HashMap::new(),
+        };
+        NatTable {
+            inner: RwLock::new(inner),
+            default_timeout,
+            protocol_timeouts: RwLock::new(HashMap::new()),
         }
-        Ok(())
     }
 
-    /// Insert a set of internal and external tuples into NatTale, this will overwrite
-    /// rows if there is a collision, so be careful before you do this.
-    pub fn insert_overwrite(&self, internal: LookupTupleIpv4, external: LookupTupleIpv4) {
-        //TODO need some sort of error here.
-        let mut nat_table = self.table.write().unwrap();
-        nat_table.insert(internal, external);
+    /// Overrides the idle timeout used for entries whose internal tuple carries `protocol`,
+    /// e.g. a short timeout for UDP and a longer one for established TCP.
+    pub fn set_protocol_timeout(&self, protocol: IpProtocol, timeout: Duration) {
+        let mut protocol_timeouts = self.protocol_timeouts.write().unwrap();
+        protocol_timeouts.insert(protocol, timeout);
+    }
+
+    fn timeout_for(&self, protocol: IpProtocol) -> Duration {
+        let protocol_timeouts = self.protocol_timeouts.read().unwrap();
+        protocol_timeouts
+            .get(&protocol)
+            .copied()
+            .unwrap_or(self.default_timeout)
     }
 
     /// Retrieve Internal Tuple given an External Tuple, returns None if
-    /// there is no entry for the given Internal Tuple.
-    /// In order to prevent borrowing confusion, we return a clone of the Tuple.
-    pub fn get_internal(&self, external: &LookupTupleIpv4) -> Option<LookupTupleIpv4> {
-        let nat_table = self.table.read().unwrap();
-        match nat_table.get_by_right(external) {
-            Some(tuple) => Some(tuple.clone()),
-            None => None,
-        }
+    /// there is no entry for the given External Tuple.
+    pub fn get_internal(&self, external: &T) -> Option<T> {
+        let inner = self.inner.read().unwrap();
+        let entry = inner.by_external.get(external)?;
+        entry.touch();
+        Some(entry.internal.clone())
     }
 
     /// Retrieve External Tuple given an Internal Tuple, returns None if
     /// there is no entry for the given Internal Tuple.
-    /// In order to prevent borrowing confusion, we return a clone of the Tuple.
-    pub fn get_external(&self, internal: &LookupTupleIpv4) -> Option<LookupTupleIpv4> {
-        let nat_table = self.table.read().unwrap();
-        match nat_table.get_by_left(internal) {
-            Some(tuple) => Some(tuple.clone()),
-            None => None,
+    pub fn get_external(&self, internal: &T) -> Option<T> {
+        let inner = self.inner.read().unwrap();
+        let entry = inner.by_internal.get(internal)?;
+        entry.touch();
+        Some(entry.external.clone())
+    }
+
+    /// Returns the per-connection metadata attached to the mapping for `internal`, if any.
+    pub fn external_data(&self, internal: &T) -> Option<Arc<E>> {
+        let inner = self.inner.read().unwrap();
+        inner
+            .by_internal
+            .get(internal)
+            .map(|entry| entry.data.clone())
+    }
+
+    /// Runs `f` against the metadata attached to `internal`'s mapping, e.g. to bump an
+    /// interior-mutable counter. Returns false if there is no mapping for `internal`.
+    pub fn update_with<F: FnOnce(&E)>(&self, internal: &T, f: F) -> bool {
+        let inner = self.inner.read().unwrap();
+        match inner.by_internal.get(internal) {
+            Some(entry) => {
+                f(&entry.data);
+                true
+            }
+            None => false,
         }
     }
 
     /// Returns True if Internal Tuple already exists in Table
-    pub fn contains_internal(&self, internal: &LookupTupleIpv4) -> bool {
-        let nat_table = self.table.read().unwrap();
-        nat_table.contains_left(internal)
+    pub fn contains_internal(&self, internal: &T) -> bool {
+        let inner = self.inner.read().unwrap();
+        inner.by_internal.contains_key(internal)
     }
 
     /// Returns True if External Tuple already exists in Table
-    pub fn contains_external(&self, external: &LookupTupleIpv4) -> bool {
-        let nat_table = self.table.read().unwrap();
-        nat_table.contains_right(external)
+    pub fn contains_external(&self, external: &T) -> bool {
+        let inner = self.inner.read().unwrap();
+        inner.by_external.contains_key(external)
     }
 
     /// Returns number of entries in the table
     pub fn len(&self) -> usize {
-        let nat_table = self.table.read().unwrap();
-        nat_table.len()
+        let inner = self.inner.read().unwrap();
+        inner.by_internal.len()
     }
 
     /// Returns true if the table is empty
     pub fn is_empty(&self) -> bool {
-        let nat_table = self.table.read().unwrap();
-        nat_table.is_empty()
+        let inner = self.inner.read().unwrap();
+        inner.by_internal.is_empty()
     }
 
     /// Clears all entries in the table
     pub fn clear(&self) {
-        let mut nat_table = self.table.write().unwrap();
-        nat_table.clear();
+        let mut inner = self.inner.write().unwrap();
+        inner.by_internal.clear();
+        inner.by_external.clear();
+    }
+
+    /// Removes the mapping for `internal`, taking the write lock once so both the internal
+    /// and external sides are dropped in a single critical section. Returns the external
+    /// tuple that was paired with it, or None if there was no such mapping.
+    pub fn remove_by_internal(&self, internal: &T) -> Option<T> {
+        let mut inner = self.inner.write().unwrap();
+        let entry = inner.by_internal.remove(internal)?;
+        inner.by_external.remove(&entry.external);
+        Some(entry.external.clone())
+    }
+
+    /// Removes the mapping for `external`, taking the write lock once so both the internal
+    /// and external sides are dropped in a single critical section. Returns the internal
+    /// tuple that was paired with it, or None if there was no such mapping.
+    pub fn remove_by_external(&self, external: &T) -> Option<T> {
+        let mut inner = self.inner.write().unwrap();
+        let entry = inner.by_external.remove(external)?;
+        inner.by_internal.remove(&entry.internal);
+        Some(entry.internal.clone())
+    }
+
+    /// Walks the table under a single write lock and removes every entry whose last-seen
+    /// time plus its timeout has elapsed, returning the number of entries removed.
+    pub fn reap_expired(&self) -> usize {
+        let now = Instant::now();
+        let mut inner = self.inner.write().unwrap();
+        let expired: Vec<(T, T)> = inner
+            .by_internal
+            .values()
+            .filter(|entry| entry.is_expired(now))
+            .map(|entry| (entry.internal.clone(), entry.external.clone()))
+            .collect();
+
+        for (internal, external) in &expired {
+            inner.by_internal.remove(internal);
+            inner.by_external.remove(external);
+        }
+        expired.len()
+    }
+}
+
+impl<T: Eq + Hash + Clone + IpTuple, E> NatTable<T, E> {
+    /// Insert a set of internal and external tuples, along with the metadata to attach to
+    /// the connection, into the NatTable. Returns an Error if either tuple already exists.
+    pub fn insert(&self, internal: T, external: T, data: E) -> Result<(), ()> {
+        let mut inner = self.inner.write().unwrap();
+        if inner.by_internal.contains_key(&internal) || inner.by_external.contains_key(&external)
+        {
+            return Err(());
+        }
+        let timeout = self.timeout_for(internal.protocol());
+        let entry = Arc::new(ConnectionEntry {
+            internal: internal.clone(),
+            external: external.clone(),
+            last_seen: RwLock::new((Instant::now(), timeout)),
+            data: Arc::new(data),
+        });
+        inner.by_internal.insert(internal, entry.clone());
+        inner.by_external.insert(external, entry);
+        Ok(())
+    }
+
+    /// Insert a set of internal and external tuples into the NatTable, this will overwrite
+    /// rows if there is a collision, so be careful before you do this.
+    pub fn insert_overwrite(&self, internal: T, external: T, data: E) {
+        let timeout = self.timeout_for(internal.protocol());
+        let entry = Arc::new(ConnectionEntry {
+            internal: internal.clone(),
+            external: external.clone(),
+            last_seen: RwLock::new((Instant::now(), timeout)),
+            data: Arc::new(data),
+        });
+        let mut inner = self.inner.write().unwrap();
+
+        // Mirror BiHashMap::insert's semantics: evict whatever entry the new pair collides
+        // with on *either* side, so no stale reverse pointer can survive the overwrite.
+        if let Some(old) = inner.by_internal.remove(&internal) {
+            inner.by_external.remove(&old.external);
+        }
+        if let Some(old) = inner.by_external.remove(&external) {
+            inner.by_internal.remove(&old.internal);
+        }
+
+        inner.by_internal.insert(internal, entry.clone());
+        inner.by_external.insert(external, entry);
+    }
+}
+
+impl<E: Default> NatTable<LookupTupleIpv4, E> {
+    /// Picks a free external (ip, port) tuple for an outbound flow and inserts the mapping,
+    /// probing candidates and inserting the winner under a single write lock so no other
+    /// caller can steal the port between the check and the insert.
+    ///
+    /// The starting port is derived from a hash of `internal` so repeated calls for the same
+    /// flow tend to probe the same region of `port_range`; candidates are then tried
+    /// sequentially, wrapping around the range, until a free one is found. Returns None if
+    /// `internal` is already mapped or the whole range is exhausted.
+    pub fn allocate_external(
+        &self,
+        internal: LookupTupleIpv4,
+        nat_ip: Ipv4Address,
+        port_range: Range<u16>,
+    ) -> Option<LookupTupleIpv4> {
+        if port_range.is_empty() {
+            return None;
+        }
+        let span = (port_range.end - port_range.start) as u32;
+
+        let mut hasher = DefaultHasher::new();
+        internal.hash(&mut hasher);
+        let start_offset = (hasher.finish() % u64::from(span)) as u32;
+
+        let timeout = self.timeout_for(internal.protocol());
+        let mut inner = self.inner.write().unwrap();
+        if inner.by_internal.contains_key(&internal) {
+            return None;
+        }
+
+        for i in 0..span {
+            let port = port_range.start.wrapping_add(((start_offset + i) % span) as u16);
+            let candidate = LookupTupleIpv4::new(
+                internal.protocol(),
+                nat_ip,
+                internal.dst_ip(),
+                port,
+                internal.dst_port(),
+            );
+            if inner.by_external.contains_key(&candidate) {
+                continue;
+            }
+
+            let entry = Arc::new(ConnectionEntry {
+                internal: internal.clone(),
+                external: candidate.clone(),
+                last_seen: RwLock::new((Instant::now(), timeout)),
+                data: Arc::new(E::default()),
+            });
+            inner.by_internal.insert(internal, entry.clone());
+            inner.by_external.insert(candidate.clone(), entry);
+            return Some(candidate);
+        }
+        None
     }
 }
 
@@ -88,19 +301,21 @@ impl NatTable {
 #[allow(dead_code)]
 mod tests {
     use super::*;
-    use crate::packet::tuple::LookupTupleIpv4;
+    use crate::packet::tuple::{LookupTuple, LookupTupleIpv4, LookupTupleIpv6};
     use smoltcp::phy::ChecksumCapabilities;
     use smoltcp::wire::*;
+    use std::sync::atomic::{AtomicU64, Ordering};
+    use std::thread::sleep;
 
     #[test]
     fn create_empty_table() {
-        let nat_table = NatTable::new();
+        let nat_table: NatTable<LookupTupleIpv4, ()> = NatTable::new();
         assert!(nat_table.is_empty());
     }
 
     #[test]
     fn insert_one_row() {
-        let nat_table = NatTable::new();
+        let nat_table: NatTable<LookupTupleIpv4, ()> = NatTable::new();
 
         // Create test tuple
         let internal_tuple = LookupTupleIpv4::new(
@@ -118,7 +333,7 @@ mod tests {
 
         // Test insertion
         nat_table
-            .insert(internal_tuple.clone(), external_tuple.clone())
+            .insert(internal_tuple.clone(), external_tuple.clone(), ())
             .unwrap();
         assert_eq!(nat_table.len(), 1);
 
@@ -131,16 +346,311 @@ mod tests {
         assert_eq!(nat_table.get_external(&internal_tuple), Some(external_tuple.clone()));
 
         // Test can't overwrite
-        assert!(nat_table.insert(internal_tuple.clone(), external_tuple.clone()).is_err());
+        assert!(nat_table.insert(internal_tuple.clone(), external_tuple.clone(), ()).is_err());
         assert_eq!(nat_table.len(), 1);
 
         //Test can overwrite
-        nat_table.insert_overwrite(internal_tuple.clone(), external_tuple.clone());
+        nat_table.insert_overwrite(internal_tuple.clone(), external_tuple.clone(), ());
         assert_eq!(nat_table.len(), 1);
         assert_eq!(nat_table.get_internal(&external_tuple), Some(internal_tuple.clone()));
 
-        //Test clear 
+        //Test clear
         nat_table.clear();
         assert!(nat_table.is_empty());
     }
+
+    #[test]
+    fn insert_overwrite_evicts_colliding_entries_on_either_side() {
+        let nat_table: NatTable<LookupTupleIpv4, ()> = NatTable::new();
+
+        let a_internal = LookupTupleIpv4::new(
+            IpProtocol::Tcp,
+            Ipv4Address::new(10, 0, 0, 1),
+            Ipv4Address::new(10, 0, 0, 2),
+            1337,
+            2000);
+        let a_external = LookupTupleIpv4::new(
+            IpProtocol::Tcp,
+            Ipv4Address::new(172, 168, 0, 1),
+            Ipv4Address::new(8, 8, 8, 8),
+            420,
+            9593);
+        let b_internal = LookupTupleIpv4::new(
+            IpProtocol::Tcp,
+            Ipv4Address::new(10, 0, 0, 3),
+            Ipv4Address::new(10, 0, 0, 4),
+            1338,
+            2001);
+        let b_external = LookupTupleIpv4::new(
+            IpProtocol::Tcp,
+            Ipv4Address::new(172, 168, 0, 2),
+            Ipv4Address::new(8, 8, 8, 9),
+            421,
+            9594);
+
+        nat_table
+            .insert(a_internal.clone(), a_external.clone(), ())
+            .unwrap();
+        nat_table
+            .insert(b_internal.clone(), b_external.clone(), ())
+            .unwrap();
+
+        // Rebind a_internal to b_external. This must evict both the stale a_external
+        // pointer and the stale b_internal pointer, leaving no orphaned reverse mapping.
+        nat_table.insert_overwrite(a_internal.clone(), b_external.clone(), ());
+
+        assert_eq!(nat_table.len(), 1);
+        assert_eq!(nat_table.get_external(&a_internal), Some(b_external.clone()));
+        assert_eq!(nat_table.get_internal(&b_external), Some(a_internal));
+        assert_eq!(nat_table.get_internal(&a_external), None);
+        assert_eq!(nat_table.get_external(&b_internal), None);
+    }
+
+    #[test]
+    fn remove_deletes_both_directions() {
+        let nat_table: NatTable<LookupTupleIpv4, ()> = NatTable::new();
+
+        let internal_tuple = LookupTupleIpv4::new(
+            IpProtocol::Tcp,
+            Ipv4Address::new(10, 0, 0, 1),
+            Ipv4Address::new(10, 0, 0, 2),
+            1337,
+            2000);
+        let external_tuple = LookupTupleIpv4::new(
+            IpProtocol::Tcp,
+            Ipv4Address::new(172, 168, 0, 1),
+            Ipv4Address::new(8, 8, 8, 8),
+            420,
+            9593);
+
+        nat_table
+            .insert(internal_tuple.clone(), external_tuple.clone(), ())
+            .unwrap();
+
+        assert_eq!(
+            nat_table.remove_by_internal(&internal_tuple),
+            Some(external_tuple.clone())
+        );
+        assert!(nat_table.is_empty());
+        assert!(!nat_table.contains_external(&external_tuple));
+        assert_eq!(nat_table.remove_by_internal(&internal_tuple), None);
+
+        nat_table
+            .insert(internal_tuple.clone(), external_tuple.clone(), ())
+            .unwrap();
+        assert_eq!(
+            nat_table.remove_by_external(&external_tuple),
+            Some(internal_tuple.clone())
+        );
+        assert!(nat_table.is_empty());
+        assert!(!nat_table.contains_internal(&internal_tuple));
+    }
+
+    #[test]
+    fn allocate_external_avoids_collisions() {
+        let nat_table: NatTable<LookupTupleIpv4, ()> = NatTable::new();
+
+        let first_internal = LookupTupleIpv4::new(
+            IpProtocol::Tcp,
+            Ipv4Address::new(10, 0, 0, 1),
+            Ipv4Address::new(8, 8, 8, 8),
+            1337,
+            443);
+        let second_internal = LookupTupleIpv4::new(
+            IpProtocol::Tcp,
+            Ipv4Address::new(10, 0, 0, 2),
+            Ipv4Address::new(8, 8, 8, 8),
+            1338,
+            443);
+        let nat_ip = Ipv4Address::new(172, 16, 0, 1);
+
+        let first_external = nat_table
+            .allocate_external(first_internal.clone(), nat_ip, 40000..40002)
+            .unwrap();
+        let second_external = nat_table
+            .allocate_external(second_internal.clone(), nat_ip, 40000..40002)
+            .unwrap();
+
+        assert_ne!(first_external, second_external);
+        assert_eq!(nat_table.get_external(&first_internal), Some(first_external));
+        assert_eq!(nat_table.get_external(&second_internal), Some(second_external));
+
+        // The range is now fully allocated.
+        let third_internal = LookupTupleIpv4::new(
+            IpProtocol::Tcp,
+            Ipv4Address::new(10, 0, 0, 3),
+            Ipv4Address::new(8, 8, 8, 8),
+            1339,
+            443);
+        assert_eq!(
+            nat_table.allocate_external(third_internal, nat_ip, 40000..40002),
+            None
+        );
+    }
+
+    #[test]
+    fn allocate_external_rejects_an_already_mapped_internal_tuple() {
+        let nat_table: NatTable<LookupTupleIpv4, ()> = NatTable::new();
+
+        let internal = LookupTupleIpv4::new(
+            IpProtocol::Tcp,
+            Ipv4Address::new(10, 0, 0, 1),
+            Ipv4Address::new(8, 8, 8, 8),
+            1337,
+            443);
+        let nat_ip = Ipv4Address::new(172, 16, 0, 1);
+
+        nat_table
+            .allocate_external(internal.clone(), nat_ip, 40000..40010)
+            .unwrap();
+
+        assert_eq!(
+            nat_table.allocate_external(internal, nat_ip, 40000..40010),
+            None
+        );
+    }
+
+    #[test]
+    fn allocate_external_rejects_a_reversed_port_range() {
+        let nat_table: NatTable<LookupTupleIpv4, ()> = NatTable::new();
+
+        let internal = LookupTupleIpv4::new(
+            IpProtocol::Tcp,
+            Ipv4Address::new(10, 0, 0, 1),
+            Ipv4Address::new(8, 8, 8, 8),
+            1337,
+            443);
+        let nat_ip = Ipv4Address::new(172, 16, 0, 1);
+
+        assert_eq!(
+            nat_table.allocate_external(internal, nat_ip, 100..50),
+            None
+        );
+    }
+
+    #[test]
+    fn reap_expired_entries() {
+        let nat_table: NatTable<LookupTupleIpv4, ()> =
+            NatTable::new_with_timeout(Duration::from_millis(10));
+
+        let internal_tuple = LookupTupleIpv4::new(
+            IpProtocol::Udp,
+            Ipv4Address::new(10, 0, 0, 1),
+            Ipv4Address::new(10, 0, 0, 2),
+            1337,
+            2000);
+        let external_tuple = LookupTupleIpv4::new(
+            IpProtocol::Udp,
+            Ipv4Address::new(172, 168, 0, 1),
+            Ipv4Address::new(8, 8, 8, 8),
+            420,
+            9593);
+
+        nat_table
+            .insert(internal_tuple.clone(), external_tuple.clone(), ())
+            .unwrap();
+        assert_eq!(nat_table.reap_expired(), 0);
+
+        sleep(Duration::from_millis(20));
+        assert_eq!(nat_table.reap_expired(), 1);
+        assert!(nat_table.is_empty());
+    }
+
+    #[test]
+    fn per_protocol_timeout_overrides_default() {
+        let nat_table: NatTable<LookupTupleIpv4, ()> =
+            NatTable::new_with_timeout(Duration::from_secs(300));
+        nat_table.set_protocol_timeout(IpProtocol::Udp, Duration::from_millis(10));
+
+        let internal_tuple = LookupTupleIpv4::new(
+            IpProtocol::Udp,
+            Ipv4Address::new(10, 0, 0, 1),
+            Ipv4Address::new(10, 0, 0, 2),
+            1337,
+            2000);
+        let external_tuple = LookupTupleIpv4::new(
+            IpProtocol::Udp,
+            Ipv4Address::new(172, 168, 0, 1),
+            Ipv4Address::new(8, 8, 8, 8),
+            420,
+            9593);
+
+        nat_table
+            .insert(internal_tuple, external_tuple, ())
+            .unwrap();
+
+        sleep(Duration::from_millis(20));
+        assert_eq!(nat_table.reap_expired(), 1);
+    }
+
+    #[test]
+    fn external_data_is_shared_and_updatable() {
+        let nat_table: NatTable<LookupTupleIpv4, AtomicU64> = NatTable::new();
+
+        let internal_tuple = LookupTupleIpv4::new(
+            IpProtocol::Tcp,
+            Ipv4Address::new(10, 0, 0, 1),
+            Ipv4Address::new(10, 0, 0, 2),
+            1337,
+            2000);
+        let external_tuple = LookupTupleIpv4::new(
+            IpProtocol::Tcp,
+            Ipv4Address::new(172, 168, 0, 1),
+            Ipv4Address::new(8, 8, 8, 8),
+            420,
+            9593);
+
+        nat_table
+            .insert(internal_tuple.clone(), external_tuple, AtomicU64::new(0))
+            .unwrap();
+
+        assert!(nat_table.update_with(&internal_tuple, |counter| {
+            counter.fetch_add(1, Ordering::SeqCst);
+        }));
+
+        let data = nat_table.external_data(&internal_tuple).unwrap();
+        assert_eq!(data.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn dual_stack_table_holds_v4_and_v6_flows() {
+        let nat_table: NatTable<LookupTuple, ()> = NatTable::new();
+
+        let v4_internal = LookupTuple::V4(LookupTupleIpv4::new(
+            IpProtocol::Tcp,
+            Ipv4Address::new(10, 0, 0, 1),
+            Ipv4Address::new(10, 0, 0, 2),
+            1337,
+            2000));
+        let v4_external = LookupTuple::V4(LookupTupleIpv4::new(
+            IpProtocol::Tcp,
+            Ipv4Address::new(172, 168, 0, 1),
+            Ipv4Address::new(8, 8, 8, 8),
+            420,
+            9593));
+
+        let v6_internal = LookupTuple::V6(LookupTupleIpv6::new(
+            IpProtocol::Tcp,
+            Ipv6Address::new(0xfd00, 0, 0, 0, 0, 0, 0, 1),
+            Ipv6Address::new(0xfd00, 0, 0, 0, 0, 0, 0, 2),
+            1337,
+            2000));
+        let v6_external = LookupTuple::V6(LookupTupleIpv6::new(
+            IpProtocol::Tcp,
+            Ipv6Address::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 1),
+            Ipv6Address::new(0x2001, 0x4860, 0, 0, 0, 0, 0, 8888),
+            420,
+            9593));
+
+        nat_table
+            .insert(v4_internal.clone(), v4_external.clone(), ())
+            .unwrap();
+        nat_table
+            .insert(v6_internal.clone(), v6_external.clone(), ())
+            .unwrap();
+
+        assert_eq!(nat_table.len(), 2);
+        assert_eq!(nat_table.get_external(&v4_internal), Some(v4_external));
+        assert_eq!(nat_table.get_external(&v6_internal), Some(v6_external));
+    }
 }
@@ -0,0 +1,184 @@
+use smoltcp::wire::{Ipv4Address, Ipv6Address};
+use std::collections::HashMap;
+use std::sync::RwLock;
+
+/// A destination or next-hop address, v4 or v6, using the same address types the NAT
+/// lookup tuples already carry.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum IpAddr {
+    V4(Ipv4Address),
+    V6(Ipv6Address),
+}
+
+impl IpAddr {
+    fn to_bytes(self) -> Vec<u8> {
+        match self {
+            IpAddr::V4(addr) => addr.0.to_vec(),
+            IpAddr::V6(addr) => addr.0.to_vec(),
+        }
+    }
+
+    fn max_prefix_len(self) -> u8 {
+        match self {
+            IpAddr::V4(_) => 32,
+            IpAddr::V6(_) => 128,
+        }
+    }
+}
+
+/// Where a matched route should send the packet: the next-hop address and the egress
+/// interface id.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct NextHop {
+    pub gateway: IpAddr,
+    pub interface: u32,
+}
+
+/// Zeroes every bit of `bytes` past `prefix_len`, so two addresses that agree on their
+/// first `prefix_len` bits mask down to the same network key.
+fn mask_bytes(bytes: &[u8], prefix_len: u8) -> Vec<u8> {
+    let mut masked = bytes.to_vec();
+    let full_bytes = (prefix_len / 8) as usize;
+    let remaining_bits = prefix_len % 8;
+    let kept_bytes = (prefix_len as usize + 7) / 8;
+
+    for byte in masked.iter_mut().skip(kept_bytes) {
+        *byte = 0;
+    }
+    if remaining_bits > 0 && full_bytes < masked.len() {
+        masked[full_bytes] &= 0xFFu8 << (8 - remaining_bits);
+    }
+    masked
+}
+
+/// A longest-prefix-match routing table, the sibling of `NatTable`'s exact-match lookups.
+/// Routes are keyed on `(prefix_len, masked network bytes)` so `lookup` can probe candidate
+/// prefix lengths from most to least specific instead of scanning every route.
+pub struct RoutingTable {
+    routes: RwLock<HashMap<(u8, Vec<u8>), NextHop>>,
+}
+
+impl RoutingTable {
+    /// Creates a new empty routing table.
+    pub fn new() -> Self {
+        RoutingTable {
+            routes: RwLock::new(HashMap::new()),
+        }
+    }
+
+    /// Adds (or overwrites) the route for `network/prefix_len`.
+    pub fn add_route(&self, network: IpAddr, prefix_len: u8, next_hop: NextHop) {
+        let key = (prefix_len, mask_bytes(&network.to_bytes(), prefix_len));
+        let mut routes = self.routes.write().unwrap();
+        routes.insert(key, next_hop);
+    }
+
+    /// Removes the route for `network/prefix_len`, returning its next hop if it existed.
+    pub fn remove_route(&self, network: IpAddr, prefix_len: u8) -> Option<NextHop> {
+        let key = (prefix_len, mask_bytes(&network.to_bytes(), prefix_len));
+        let mut routes = self.routes.write().unwrap();
+        routes.remove(&key)
+    }
+
+    /// Resolves `dst` to the most specific matching route, masking down from the widest
+    /// possible prefix length (/32 for IPv4, /128 for IPv6) to /0.
+    pub fn lookup(&self, dst: IpAddr) -> Option<NextHop> {
+        let routes = self.routes.read().unwrap();
+        let dst_bytes = dst.to_bytes();
+
+        for prefix_len in (0..=dst.max_prefix_len()).rev() {
+            let key = (prefix_len, mask_bytes(&dst_bytes, prefix_len));
+            if let Some(next_hop) = routes.get(&key) {
+                return Some(next_hop.clone());
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn longest_prefix_wins() {
+        let table = RoutingTable::new();
+
+        let default_route = NextHop {
+            gateway: IpAddr::V4(Ipv4Address::new(10, 0, 0, 1)),
+            interface: 0,
+        };
+        let specific_route = NextHop {
+            gateway: IpAddr::V4(Ipv4Address::new(10, 0, 0, 2)),
+            interface: 1,
+        };
+
+        table.add_route(IpAddr::V4(Ipv4Address::new(0, 0, 0, 0)), 0, default_route.clone());
+        table.add_route(
+            IpAddr::V4(Ipv4Address::new(192, 168, 1, 0)),
+            24,
+            specific_route.clone(),
+        );
+
+        assert_eq!(
+            table.lookup(IpAddr::V4(Ipv4Address::new(192, 168, 1, 42))),
+            Some(specific_route)
+        );
+        assert_eq!(
+            table.lookup(IpAddr::V4(Ipv4Address::new(8, 8, 8, 8))),
+            Some(default_route)
+        );
+    }
+
+    #[test]
+    fn lookup_misses_without_a_default_route() {
+        let table = RoutingTable::new();
+        assert_eq!(
+            table.lookup(IpAddr::V4(Ipv4Address::new(192, 168, 1, 1))),
+            None
+        );
+    }
+
+    #[test]
+    fn remove_route_deletes_the_exact_prefix() {
+        let table = RoutingTable::new();
+        let next_hop = NextHop {
+            gateway: IpAddr::V4(Ipv4Address::new(10, 0, 0, 1)),
+            interface: 2,
+        };
+
+        table.add_route(IpAddr::V4(Ipv4Address::new(172, 16, 0, 0)), 16, next_hop.clone());
+        assert_eq!(
+            table.remove_route(IpAddr::V4(Ipv4Address::new(172, 16, 0, 0)), 16),
+            Some(next_hop)
+        );
+        assert_eq!(
+            table.lookup(IpAddr::V4(Ipv4Address::new(172, 16, 5, 5))),
+            None
+        );
+    }
+
+    #[test]
+    fn ipv6_longest_prefix_match() {
+        let table = RoutingTable::new();
+        let next_hop = NextHop {
+            gateway: IpAddr::V6(Ipv6Address::new(0xfd00, 0, 0, 0, 0, 0, 0, 1)),
+            interface: 3,
+        };
+
+        table.add_route(
+            IpAddr::V6(Ipv6Address::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 0)),
+            32,
+            next_hop.clone(),
+        );
+
+        assert_eq!(
+            table.lookup(IpAddr::V6(Ipv6Address::new(0x2001, 0xdb8, 1, 2, 3, 4, 5, 6))),
+            Some(next_hop)
+        );
+        assert_eq!(
+            table.lookup(IpAddr::V6(Ipv6Address::new(0x2001, 0xdb9, 0, 0, 0, 0, 0, 1))),
+            None
+        );
+    }
+}